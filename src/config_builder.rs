@@ -0,0 +1,154 @@
+//! Build the application configuration from CLI inputs.
+use bluer::Address;
+use clap::Parser;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// Command-line arguments accepted by the application.
+#[derive(Parser, Debug)]
+#[command(author, version, about)]
+struct CliArgs {
+    /// Sensor to monitor, given as `<address>=<room>` (may be repeated).
+    #[arg(long = "sensor", value_parser = parse_sensor)]
+    sensors: Vec<(Address, String)>,
+
+    /// Advertising/measurement interval (seconds) to provision on a sensor
+    /// before passively listening to it, given as `<address>=<seconds>` (may
+    /// be repeated). Sensors without an entry are left untouched.
+    #[arg(long = "sensor-interval", value_parser = parse_sensor_interval)]
+    sensor_intervals: Vec<(Address, u16)>,
+
+    /// Bind key for a sensor with encrypted BThome v2 advertising enabled,
+    /// given as `<address>=<32 hex digit key>` (may be repeated).
+    #[arg(long = "sensor-bindkey", value_parser = parse_sensor_bindkey)]
+    sensor_bindkeys: Vec<(Address, [u8; 16])>,
+
+    /// InfluxDB connection URL.
+    #[arg(long, default_value = "http://localhost:8086")]
+    influx_conn: String,
+
+    /// InfluxDB database name.
+    #[arg(long, default_value = "lywsd03mmc")]
+    influx_database: String,
+
+    /// InfluxDB measurement name.
+    #[arg(long, default_value = "weather")]
+    influx_measurement: String,
+
+    /// InfluxDB measurement name for sensor online/offline transitions.
+    #[arg(long, default_value = "online")]
+    influx_online_measurement: String,
+
+    /// Delay (seconds) without any advertisement from a sensor before it is
+    /// considered offline.
+    #[arg(long, default_value_t = 600)]
+    watchdog_timeout_secs: u64,
+
+    /// InfluxDB username, if authentication is required.
+    #[arg(long, requires = "influx_password")]
+    influx_username: Option<String>,
+
+    /// InfluxDB password, if authentication is required.
+    #[arg(long, requires = "influx_username")]
+    influx_password: Option<String>,
+
+    /// Do not write to InfluxDB; samples are printed only with `--be-verbose`.
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print every decoded sample. Online/offline transitions are printed
+    /// with or without this flag.
+    #[arg(long, short = 'v')]
+    be_verbose: bool,
+
+    /// Replay advertisements recorded with `--capture-file` instead of
+    /// connecting to a Bluetooth adapter.
+    #[arg(long)]
+    replay_file: Option<PathBuf>,
+
+    /// Append every live advertisement to this file, newline-delimited, so
+    /// it can be replayed later with `--replay-file`.
+    #[arg(long)]
+    capture_file: Option<PathBuf>,
+}
+
+fn parse_sensor(s: &str) -> Result<(Address, String), String> {
+    let (addr, room) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid sensor mapping `{s}`, expected `<address>=<room>`"))?;
+    let addr = addr
+        .parse::<Address>()
+        .map_err(|e| format!("invalid sensor address `{addr}`: {e}"))?;
+    Ok((addr, room.to_owned()))
+}
+
+fn parse_sensor_bindkey(s: &str) -> Result<(Address, [u8; 16]), String> {
+    let (addr, key_hex) = s.split_once('=').ok_or_else(|| {
+        format!("invalid sensor bindkey `{s}`, expected `<address>=<32 hex digit key>`")
+    })?;
+    let addr = addr
+        .parse::<Address>()
+        .map_err(|e| format!("invalid sensor address `{addr}`: {e}"))?;
+    let key = hex::decode(key_hex).map_err(|e| format!("invalid bindkey `{key_hex}`: {e}"))?;
+    let key: [u8; 16] = key
+        .try_into()
+        .map_err(|_| format!("bindkey `{key_hex}` must be exactly 16 bytes (32 hex digits)"))?;
+    Ok((addr, key))
+}
+
+fn parse_sensor_interval(s: &str) -> Result<(Address, u16), String> {
+    let (addr, interval) = s
+        .split_once('=')
+        .ok_or_else(|| format!("invalid sensor interval `{s}`, expected `<address>=<seconds>`"))?;
+    let addr = addr
+        .parse::<Address>()
+        .map_err(|e| format!("invalid sensor address `{addr}`: {e}"))?;
+    let interval = interval
+        .parse::<u16>()
+        .map_err(|e| format!("invalid interval `{interval}`: {e}"))?;
+    Ok((addr, interval))
+}
+
+/// Fully resolved application configuration.
+#[derive(Debug)]
+pub struct AppConfig {
+    pub sensors_names: HashMap<Address, String>,
+    pub sensor_intervals: HashMap<Address, u16>,
+    pub sensor_bindkeys: HashMap<Address, [u8; 16]>,
+    pub influx_conn: String,
+    pub influx_database: String,
+    pub influx_measurement: String,
+    pub influx_online_measurement: String,
+    pub influx_credentials: Option<(String, String)>,
+    pub dry_run: bool,
+    pub be_verbose: bool,
+    pub watchdog_timeout: Duration,
+    pub replay_file: Option<PathBuf>,
+    pub capture_file: Option<PathBuf>,
+}
+
+impl AppConfig {
+    /// Parse CLI arguments and build the resolved configuration.
+    pub fn get_from_cli_inputs() -> Result<Self, String> {
+        let args = CliArgs::parse();
+        Ok(Self {
+            sensors_names: args.sensors.into_iter().collect(),
+            sensor_intervals: args.sensor_intervals.into_iter().collect(),
+            sensor_bindkeys: args.sensor_bindkeys.into_iter().collect(),
+            influx_conn: args.influx_conn,
+            influx_database: args.influx_database,
+            influx_measurement: args.influx_measurement,
+            influx_online_measurement: args.influx_online_measurement,
+            influx_credentials: match (args.influx_username, args.influx_password) {
+                (Some(u), Some(p)) => Some((u, p)),
+                _ => None,
+            },
+            dry_run: args.dry_run,
+            be_verbose: args.be_verbose,
+            watchdog_timeout: Duration::from_secs(args.watchdog_timeout_secs),
+            replay_file: args.replay_file,
+            capture_file: args.capture_file,
+        })
+    }
+}
@@ -1,8 +1,7 @@
 //! Entry point of the application: listen to bluetooth advertisements
 //! and call the sample handlers when appropriate.
-use crate::sample_handler::SensorHandler;
-use bluer::{Adapter, AdapterEvent, Address, DeviceEvent, DeviceProperty};
-use btsensor::bthome::v2::BtHomeV2;
+use crate::sample_handler::{SensorHandler, SensorMessage};
+use bluer::{Adapter, AdapterEvent, Address, DeviceEvent, DeviceProperty, Uuid};
 use config_builder::AppConfig;
 use futures::{pin_mut, stream::SelectAll, Stream, StreamExt};
 use influxdb::Client;
@@ -11,14 +10,25 @@ use tokio::sync::{
     mpsc::{self, Sender},
     Mutex,
 };
+use uuid::uuid;
 
 mod config_builder;
+mod replay;
 mod sample_handler;
 
 /// Magic UUID value for advertised weather data, see the definition of the
 /// [custom format](https://github.com/pvvx/ATC_MiThermometer/blob/master/README.md#custom-format-all-data-little-endian).
 const WEATHER_SAMPLE_UUID_HEADER: u32 = 0x0000fcd2;
 
+/// UUID of the pvvx custom configuration GATT service.
+const PVVX_CONFIG_SERVICE_UUID: Uuid = uuid!("00001f10-0000-1000-8000-00805f9b34fb");
+/// UUID of the writable configuration characteristic inside that service.
+const PVVX_CONFIG_CHAR_UUID: Uuid = uuid!("00001f1f-0000-1000-8000-00805f9b34fb");
+/// Opcode for the "set measurement/advertising interval" command accepted by
+/// the pvvx configuration characteristic: one byte identifying the setting,
+/// followed by the little-endian value (here, the interval in seconds).
+const CMD_SET_ADVERTISING_INTERVAL: u8 = 0x23;
+
 /// Setup the InfluxDb connector, wrapped in Arc and (tokio) Mutex, ready for subsequent usage.
 fn setup_influx_connection(app_config: &AppConfig) -> Option<Arc<Mutex<Client>>> {
     match app_config.dry_run {
@@ -81,25 +91,112 @@ async fn handle_adapter_evt<'a>(
     }
 }
 
+/// Connect to each sensor for which an advertising interval is configured,
+/// write it over the pvvx configuration characteristic, read it back to
+/// confirm, then disconnect so that passive advertising resumes. Run once at
+/// startup, before entering the passive discovery loop.
+///
+/// A sensor that cannot be provisioned (out of range, not connectable, GATT
+/// error...) is logged and skipped rather than aborting the whole process, so
+/// that one unreachable sensor at startup does not stop monitoring of the
+/// other configured sensors.
+async fn configure_sensors(adapter: &Adapter, app_config: &AppConfig) {
+    for (addr, interval) in &app_config.sensor_intervals {
+        let room = app_config
+            .sensors_names
+            .get(addr)
+            .map(String::as_str)
+            .unwrap_or("?");
+        println!("Configuring device {addr} (room: {room}): advertising interval {interval}s");
+
+        if let Err(e) = configure_sensor(adapter, *addr, *interval).await {
+            println!("Device {addr} (room: {room}): configuration failed: {e}");
+        }
+    }
+}
+
+/// Provision a single sensor; see [`configure_sensors`].
+async fn configure_sensor(adapter: &Adapter, addr: Address, interval: u16) -> bluer::Result<()> {
+    let device = adapter.device(addr)?;
+    device.connect().await?;
+
+    let result = async {
+        let mut found = false;
+        for service in device.services().await? {
+            if service.uuid().await? != PVVX_CONFIG_SERVICE_UUID {
+                continue;
+            }
+            for characteristic in service.characteristics().await? {
+                if characteristic.uuid().await? != PVVX_CONFIG_CHAR_UUID {
+                    continue;
+                }
+                found = true;
+
+                let mut command = vec![CMD_SET_ADVERTISING_INTERVAL];
+                command.extend_from_slice(&interval.to_le_bytes());
+                characteristic.write(&command).await?;
+
+                let readback = characteristic.read().await?;
+                if readback.get(1..3) == Some(interval.to_le_bytes().as_slice()) {
+                    println!("Device {addr}: advertising interval confirmed");
+                } else {
+                    println!("Device {addr}: advertising interval not confirmed");
+                }
+            }
+        }
+        if !found {
+            println!("Device {addr}: pvvx configuration service not found");
+        }
+        Ok(())
+    }
+    .await;
+
+    if let Err(e) = device.disconnect().await {
+        println!("Device {addr}: disconnect failed: {e}");
+    }
+    result
+}
+
 /// Handle a PropertyChanged event on a bluetooth device: filter the stream,
-/// looking for data advertisement with the correct UUID.
+/// looking for data advertisement with the correct UUID, and track the
+/// device's radio metrics (RSSI, TX power) as they are reported.
 async fn handle_dev_changed_prop_evt(
+    addr: Address,
     changed_property: DeviceProperty,
-    sender: &mut Sender<BtHomeV2>,
+    sender: &mut Sender<SensorMessage>,
+    app_config: &AppConfig,
 ) {
-    if let DeviceProperty::ServiceData(service_data) = changed_property {
-        for (uuid, raw_sample) in &service_data {
-            if uuid.as_fields().0 == WEATHER_SAMPLE_UUID_HEADER {
-                match BtHomeV2::decode(raw_sample) {
-                    Ok(bthome) => {
-                        if let Err(e) = sender.send(bthome).await {
-                            println!("{e}");
-                        };
+    match changed_property {
+        DeviceProperty::ServiceData(service_data) => {
+            for (uuid, raw_sample) in &service_data {
+                if uuid.as_fields().0 == WEATHER_SAMPLE_UUID_HEADER {
+                    if let Some(capture_file) = &app_config.capture_file {
+                        if let Err(e) =
+                            replay::capture(capture_file, addr, uuid.as_fields().0, raw_sample)
+                        {
+                            println!("Capture failed: {e}");
+                        }
+                    }
+                    if let Err(e) = sender
+                        .send(SensorMessage::Raw(raw_sample.clone(), chrono::Utc::now()))
+                        .await
+                    {
+                        println!("{e}");
                     }
-                    Err(e) => println!("{e}"),
-                };
+                }
             }
         }
+        DeviceProperty::Rssi(rssi) => {
+            if let Err(e) = sender.send(SensorMessage::Rssi(rssi.into())).await {
+                println!("{e}");
+            }
+        }
+        DeviceProperty::TxPower(tx_power) => {
+            if let Err(e) = sender.send(SensorMessage::TxPower(tx_power.into())).await {
+                println!("{e}");
+            }
+        }
+        _ => {}
     }
 }
 
@@ -110,6 +207,7 @@ async fn main() -> bluer::Result<()> {
     let influx_client = setup_influx_connection(&app_config);
 
     let mut channels = HashMap::new();
+    let mut sensor_tasks = Vec::new();
     for (addr, room) in &app_config.sensors_names {
         let (send, recv) = mpsc::channel(16);
         channels.insert(addr, send);
@@ -117,15 +215,32 @@ async fn main() -> bluer::Result<()> {
             *addr,
             room.to_owned(),
             recv,
+            app_config.sensor_bindkeys.get(addr).copied(),
             influx_client.clone(),
             app_config.influx_measurement.clone(),
+            app_config.influx_online_measurement.clone(),
             app_config.be_verbose,
+            app_config.watchdog_timeout,
         );
-        tokio::spawn(async move { sensor_handler.run().await });
+        sensor_tasks.push(tokio::spawn(async move { sensor_handler.run().await }));
+    }
+
+    // Replay mode bypasses the bluetooth adapter entirely: feed previously
+    // captured advertisements through the same channels and exit once done.
+    if let Some(replay_file) = &app_config.replay_file {
+        if let Err(e) = replay::replay(replay_file, &mut channels).await {
+            println!("Replay failed: {e}");
+        }
+        drop(channels);
+        for task in sensor_tasks {
+            let _ = task.await;
+        }
+        return Ok(());
     }
 
     let mut device_events = SelectAll::new();
     let (adapter_events, adapter) = setup_bluetooth_adapter().await?;
+    configure_sensors(&adapter, &app_config).await;
     pin_mut!(adapter_events);
 
     loop {
@@ -140,7 +255,7 @@ async fn main() -> bluer::Result<()> {
             Some((DeviceEvent::PropertyChanged(prop), addr)) = device_events.next() => {
                 // Handle a new event related to a linked device
                 if let Some(sender)=channels.get_mut(&addr){
-                    handle_dev_changed_prop_evt(prop, sender).await;
+                    handle_dev_changed_prop_evt(addr, prop, sender, &app_config).await;
                 }
             },
             else => break